@@ -1,7 +1,15 @@
 use structopt::StructOpt;
-use x11::{xlib, xtest};
+use x11::{xlib, xrecord, xtest};
 
 use std::ffi::CString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::os::raw::c_char;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
 
 type Error = Box<dyn std::error::Error>;
 
@@ -11,15 +19,15 @@ struct Opts {
     #[structopt(
         long,
         index(1),
-        required_unless_one(&["mouse", "dump"]),
-        conflicts_with_all(&["mouse", "dump"])
+        required_unless_one(&["mouse", "dump", "play", "type", "record", "listen", "move"]),
+        conflicts_with_all(&["mouse", "dump", "play", "type", "record", "listen", "move"])
     )]
     key: Option<String>,
     #[structopt(
         name = "mouse",
         long,
-        required_unless_one(&["key", "dump"]),
-        conflicts_with_all(&["key", "dump"])
+        required_unless_one(&["key", "dump", "play", "type", "record", "listen", "move"]),
+        conflicts_with_all(&["key", "dump", "play", "type", "record", "listen", "move"])
     )]
 
     /// The integer index of the mouse button to be pressed
@@ -30,39 +38,164 @@ struct Opts {
     release: bool,
     #[structopt(
         long,
-        required_unless_one(&["key", "mouse"]),
-        conflicts_with_all(&["key", "mouse"])
+        required_unless_one(&["key", "mouse", "play", "type", "record", "listen", "move"]),
+        conflicts_with_all(&["key", "mouse", "play", "type", "record", "listen", "move"])
     )]
 
     /// Print a list of all the available keys in the current keymap
     dump: bool,
+
+    #[structopt(
+        long,
+        required_unless_one(&["key", "mouse", "dump", "type", "record", "listen", "move"]),
+        conflicts_with_all(&["key", "mouse", "dump", "type", "record", "listen", "move"])
+    )]
+
+    /// Play back a macro script of recorded xmacro-style instructions
+    play: Option<PathBuf>,
+
+    #[structopt(
+        name = "type",
+        long,
+        required_unless_one(&["key", "mouse", "dump", "play", "record", "listen", "move"]),
+        conflicts_with_all(&["key", "mouse", "dump", "play", "record", "listen", "move"])
+    )]
+
+    /// Type out a UTF-8 string, synthesizing a temporary keymap binding for
+    /// any character not already present in the current keymap
+    type_string: Option<String>,
+
+    #[structopt(long, default_value = "10")]
+
+    /// Milliseconds to wait between each character sent by `--type`
+    type_delay: u64,
+
+    #[structopt(
+        long,
+        required_unless_one(&["key", "mouse", "dump", "play", "type", "listen", "move"]),
+        conflicts_with_all(&["key", "mouse", "dump", "play", "type", "listen", "move"])
+    )]
+
+    /// Record key/button/motion events to a macro file in the same format
+    /// consumed by `--play`
+    record: Option<PathBuf>,
+
+    #[structopt(long, default_value = "Escape")]
+
+    /// The key that stops an in-progress `--record` session
+    stop_key: String,
+
+    #[structopt(
+        long,
+        required_unless_one(&["key", "mouse", "dump", "play", "type", "record", "move"]),
+        conflicts_with_all(&["key", "mouse", "dump", "play", "type", "record", "move"])
+    )]
+
+    /// Run as a daemon that grabs the keys/buttons bound in the given config
+    /// file and forwards them as their mapped target event
+    listen: Option<PathBuf>,
+
+    #[structopt(
+        name = "move",
+        long,
+        number_of_values(2),
+        value_names(&["X", "Y"]),
+        required_unless_one(&["key", "mouse", "dump", "play", "type", "record", "listen"]),
+        conflicts_with_all(&["key", "mouse", "dump", "play", "type", "record", "listen"])
+    )]
+
+    /// Move the pointer to (or, with `--relative`, by) the given coordinates
+    move_to: Vec<i32>,
+
+    #[structopt(long)]
+
+    /// Treat the `--move` coordinates as relative to the pointer's current position
+    relative: bool,
+
+    #[structopt(long)]
+
+    /// A '+' separated chord of modifiers (ctrl, shift, super, alt) to hold
+    /// while the `--key` event is sent; the key is then pressed and released
+    /// as a unit, so `--release` has no effect on it
+    mods: Option<String>,
+
+    #[structopt(long, default_value = "x11", possible_values = &["x11", "uinput"])]
+
+    /// The event-sending backend to use for `--key`/`--mouse`; `uinput`
+    /// works outside an X11 session (Wayland, a bare TTY)
+    backend: BackendKind,
 }
 
 fn main() -> Result<(), Error> {
     let opts = Opts::from_args();
 
-    let mut display = Display::new()?;
+    if opts.dump
+        || opts.play.is_some()
+        || opts.type_string.is_some()
+        || opts.record.is_some()
+        || opts.listen.is_some()
+        || !opts.move_to.is_empty()
+    {
+        let mut display = Display::new()?;
 
-    if opts.dump {
-        display.dump();
-    } else {
-        let state = if opts.release {
-            ButtonState::Released
+        if opts.dump {
+            display.dump();
+        } else if let Some(path) = &opts.play {
+            display.play(path)?;
+        } else if let Some(text) = &opts.type_string {
+            display.send_string(text, opts.type_delay)?;
+        } else if let Some(path) = &opts.record {
+            display.record(path, &opts.stop_key)?;
+        } else if let Some(path) = &opts.listen {
+            display.listen(path)?;
         } else {
-            ButtonState::Pressed
-        };
+            display.move_pointer(opts.move_to[0], opts.move_to[1], opts.relative)?;
+        }
+
+        return Ok(());
+    }
+
+    let state = if opts.release {
+        ButtonState::Released
+    } else {
+        ButtonState::Pressed
+    };
+
+    // Backend is a shared send_key/send_button/flush surface, but it can't
+    // express modifier chords, so --mods needs the real XTest Display and
+    // isn't available through the uinput backend
+    if let Some(mods) = &opts.mods {
+        if opts.backend != BackendKind::X11 {
+            return Err("--mods is only supported with the x11 backend".into());
+        }
+
+        let modifiers = parse_modifiers(mods)?;
+        let mut display = Display::new()?;
 
         match (&opts.key, &opts.mouse_button) {
-            (Some(key), None) => {
-                display.send_key(key, state)?;
-            }
-            (None, Some(mouse)) => {
-                display.send_button(*mouse, state)?;
-            }
+            (Some(key), None) => display.send_key(key, state, &modifiers)?,
+            (None, Some(mouse)) => display.send_button(*mouse, state)?,
             _ => unreachable!("Either <mouse> or <key> must be suplied"),
         }
+
+        display.flush();
+
+        return Ok(());
+    }
+
+    let mut backend: Box<dyn Backend> = match opts.backend {
+        BackendKind::X11 => Box::new(Display::new()?),
+        BackendKind::Uinput => Box::new(UinputBackend::new()?),
+    };
+
+    match (&opts.key, &opts.mouse_button) {
+        (Some(key), None) => backend.send_key(key, state)?,
+        (None, Some(mouse)) => backend.send_button(*mouse, state)?,
+        _ => unreachable!("Either <mouse> or <key> must be suplied"),
     }
 
+    backend.flush();
+
     Ok(())
 }
 
@@ -72,8 +205,77 @@ pub enum ButtonState {
     Released,
 }
 
+/// A single parsed line of an xmacro-compatible macro script
+#[derive(Clone, Debug)]
+enum Instruction {
+    KeyPress(String),
+    KeyRelease(String),
+    ButtonPress(u32),
+    ButtonRelease(u32),
+    Delay(u64),
+    Motion(i32, i32),
+    ExecBlock(String),
+    ExecNoBlock(String),
+}
+
+impl Instruction {
+    fn parse_line(line: &str) -> Result<Option<Self>, Error> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let command = parts.next().unwrap_or_default();
+        let rest = parts.next().unwrap_or_default().trim();
+
+        let instruction = match command {
+            "KeyStrPress" => Instruction::KeyPress(rest.to_string()),
+            "KeyStrRelease" => Instruction::KeyRelease(rest.to_string()),
+            "ButtonPress" => Instruction::ButtonPress(rest.parse()?),
+            "ButtonRelease" => Instruction::ButtonRelease(rest.parse()?),
+            "Delay" => Instruction::Delay(rest.parse()?),
+            "MotionNotify" => {
+                let mut coords = rest.split_whitespace();
+                let x = coords
+                    .next()
+                    .ok_or("MotionNotify missing x coordinate")?
+                    .parse()?;
+                let y = coords
+                    .next()
+                    .ok_or("MotionNotify missing y coordinate")?
+                    .parse()?;
+                Instruction::Motion(x, y)
+            }
+            "ExecBlock" => Instruction::ExecBlock(rest.to_string()),
+            "ExecNoBlock" => Instruction::ExecNoBlock(rest.to_string()),
+            _ => return Err(format!("Unrecognized macro instruction '{}'", command).into()),
+        };
+
+        Ok(Some(instruction))
+    }
+
+    fn parse_file(path: &std::path::Path) -> Result<Vec<Self>, Error> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+
+        let mut instructions = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            match Self::parse_line(&line) {
+                Ok(Some(instruction)) => instructions.push(instruction),
+                Ok(None) => {}
+                Err(err) => return Err(format!("Line {}: {}", number + 1, err).into()),
+            }
+        }
+
+        Ok(instructions)
+    }
+}
+
 pub struct Display {
     display: *mut xlib::Display,
+    grabs: Vec<Grab>,
 }
 
 impl Display {
@@ -82,7 +284,10 @@ impl Display {
         if display.is_null() {
             Err("Could not acquire XDisplay".into())
         } else {
-            let display = Display { display };
+            let display = Display {
+                display,
+                grabs: Vec::new(),
+            };
 
             Ok(display)
         }
@@ -114,22 +319,129 @@ impl Display {
         }
     }
 
-    pub fn send_key(&mut self, key: &str, state: ButtonState) -> Result<(), Error> {
+    /// Read and execute a macro script, validating every instruction before
+    /// sending any events so a bad line fails fast instead of mid-playback
+    pub fn play(&mut self, path: &std::path::Path) -> Result<(), Error> {
+        let instructions = Instruction::parse_file(path)?;
+
+        for instruction in &instructions {
+            match instruction {
+                Instruction::KeyPress(key) | Instruction::KeyRelease(key) => {
+                    self.validate_key(key)?;
+                }
+                Instruction::ButtonPress(button) | Instruction::ButtonRelease(button) => {
+                    self.validate_button(*button)?;
+                }
+                _ => {}
+            }
+        }
+
+        for instruction in instructions {
+            match instruction {
+                Instruction::KeyPress(key) => self.send_key(&key, ButtonState::Pressed, &[])?,
+                Instruction::KeyRelease(key) => self.send_key(&key, ButtonState::Released, &[])?,
+                Instruction::ButtonPress(button) => {
+                    self.send_button(button, ButtonState::Pressed)?
+                }
+                Instruction::ButtonRelease(button) => {
+                    self.send_button(button, ButtonState::Released)?
+                }
+                Instruction::Delay(millis) => sleep(Duration::from_millis(millis)),
+                Instruction::Motion(x, y) => self.move_pointer(x, y, false)?,
+                Instruction::ExecBlock(cmd) => {
+                    Command::new("sh").arg("-c").arg(&cmd).status()?;
+                }
+                Instruction::ExecNoBlock(cmd) => {
+                    Command::new("sh").arg("-c").arg(&cmd).spawn()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn keysym_for(&self, key: &str) -> Result<xlib::KeySym, Error> {
         let c_key = CString::new(key)?;
         let keysym = unsafe { xlib::XStringToKeysym(c_key.as_ptr()) };
         if keysym as i32 == xlib::NoSymbol {
             return Err(format!("Key '{}' not found", key).into());
         }
+
+        Ok(keysym)
+    }
+
+    fn validate_key(&mut self, key: &str) -> Result<(), Error> {
+        let keysym = self.keysym_for(key)?;
         let keycode = unsafe { xlib::XKeysymToKeycode(self.display, keysym) };
         if !self.keycode_range().contains(&(keycode as i32)) {
             return Err(format!("Keycode for keysym of '{}' not found", key).into());
         }
+
+        Ok(())
+    }
+
+    fn validate_button(&self, button: u32) -> Result<(), Error> {
+        if button > 10 {
+            return Err(format!("Mouse button '{}' out of range", button).into());
+        }
+
+        Ok(())
+    }
+
+    /// Send a key event, optionally holding a chord of `modifiers` for its
+    /// duration. With modifiers present the key is always pressed and
+    /// released as a unit; `state` only controls the bare single-key case
+    pub fn send_key(
+        &mut self,
+        key: &str,
+        state: ButtonState,
+        modifiers: &[&str],
+    ) -> Result<(), Error> {
+        let mut mod_keycodes = Vec::with_capacity(modifiers.len());
+        for modifier in modifiers {
+            let keysym = self.keysym_for(modifier)?;
+            let keycode = unsafe { xlib::XKeysymToKeycode(self.display, keysym) };
+            if !self.keycode_range().contains(&(keycode as i32)) {
+                return Err(format!("Keycode for modifier '{}' not found", modifier).into());
+            }
+            mod_keycodes.push(keycode);
+        }
+
+        let keysym = self.keysym_for(key)?;
+        let keycode = unsafe { xlib::XKeysymToKeycode(self.display, keysym) };
+        if !self.keycode_range().contains(&(keycode as i32)) {
+            return Err(format!("Keycode for keysym of '{}' not found", key).into());
+        }
+
+        for &mod_keycode in &mod_keycodes {
+            self.raw_key_event(mod_keycode, ButtonState::Pressed);
+        }
+
+        if mod_keycodes.is_empty() {
+            self.raw_key_event(keycode, state);
+        } else {
+            self.raw_key_event(keycode, ButtonState::Pressed);
+            self.raw_key_event(keycode, ButtonState::Released);
+        }
+
+        for &mod_keycode in mod_keycodes.iter().rev() {
+            self.raw_key_event(mod_keycode, ButtonState::Released);
+        }
+
+        self.flush();
+
+        Ok(())
+    }
+
+    pub fn send_button(&mut self, button: u32, state: ButtonState) -> Result<(), Error> {
+        self.validate_button(button)?;
+
         let pressed = match state {
             ButtonState::Pressed => 1,
             ButtonState::Released => 0,
         };
         unsafe {
-            xtest::XTestFakeKeyEvent(self.display, keycode as u32, pressed, 0);
+            xtest::XTestFakeButtonEvent(self.display, button, pressed, 0);
         }
 
         self.flush();
@@ -137,17 +449,134 @@ impl Display {
         Ok(())
     }
 
-    pub fn send_button(&mut self, button: u32, state: ButtonState) -> Result<(), Error> {
-        if button > 10 {
-            return Err(format!("Mouse button '{}' out of range", button).into());
+    /// Type out a whole string, one synthesized keysym at a time. Characters
+    /// missing from the current keymap are temporarily bound to an unused
+    /// keycode, used, and then unbound so the keymap is left as found
+    pub fn send_string(&mut self, text: &str, delay_ms: u64) -> Result<(), Error> {
+        for ch in text.chars() {
+            self.send_char(ch)?;
+            if delay_ms > 0 {
+                sleep(Duration::from_millis(delay_ms));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_char(&mut self, ch: char) -> Result<(), Error> {
+        let codepoint = ch as xlib::KeySym;
+        // Latin-1 codepoints map directly to their keysym, everything else
+        // uses the Unicode keysym range described in the X11 keysym spec
+        let keysym = if codepoint < 0x100 {
+            codepoint
+        } else {
+            0x01000000 + codepoint
+        };
+
+        let mut keycode = unsafe { xlib::XKeysymToKeycode(self.display, keysym) };
+        // Held for its Drop side effect: restores the stolen keycode's prior
+        // mapping on every exit path, including an early `?` return below
+        let _guard = if keycode == 0 {
+            let (code, original) = self.bind_temporary_keysym(keysym)?;
+            keycode = code;
+            Some(TemporaryKeysymGuard {
+                display: self.display,
+                keycode: code,
+                original,
+            })
+        } else {
+            None
+        };
+
+        let level0 = unsafe { xlib::XKeycodeToKeysym(self.display, keycode, 0) };
+        let level1 = unsafe { xlib::XKeycodeToKeysym(self.display, keycode, 1) };
+        let shift = keysym != level0 && keysym == level1;
+
+        let shift_keycode = if shift {
+            Some(self.keycode_for_keysym(x11::keysym::XK_Shift_L as xlib::KeySym)?)
+        } else {
+            None
+        };
+
+        if let Some(shift_keycode) = shift_keycode {
+            self.raw_key_event(shift_keycode, ButtonState::Pressed);
+        }
+
+        self.raw_key_event(keycode, ButtonState::Pressed);
+        self.raw_key_event(keycode, ButtonState::Released);
+
+        if let Some(shift_keycode) = shift_keycode {
+            self.raw_key_event(shift_keycode, ButtonState::Released);
+        }
+
+        // raw_key_event only queues XTestFakeKeyEvent calls; flush here so
+        // `--type-delay` actually spaces out delivered events rather than
+        // just stalling the client while everything sits buffered
+        self.flush();
+
+        Ok(())
+    }
+
+    fn keycode_for_keysym(&mut self, keysym: xlib::KeySym) -> Result<u8, Error> {
+        let keycode = unsafe { xlib::XKeysymToKeycode(self.display, keysym) };
+        if keycode == 0 {
+            return Err(format!("No keycode bound for keysym '{}'", keysym).into());
+        }
+
+        Ok(keycode)
+    }
+
+    /// Bind `keysym` to an unused keycode so it can be sent even though the
+    /// current keymap has no key for it, returning the keycode used and its
+    /// prior mapping so the caller can restore it afterwards
+    fn bind_temporary_keysym(&mut self, keysym: xlib::KeySym) -> Result<(u8, Vec<xlib::KeySym>), Error> {
+        let keycode = self
+            .keycode_range()
+            .rev()
+            .find(|&code| unsafe { xlib::XKeycodeToKeysym(self.display, code as u8, 0) } == 0)
+            .ok_or("No unused keycode available to bind temporary keysym")? as u8;
+
+        let mut keysyms_per_keycode = 0;
+        let original = unsafe {
+            let raw = xlib::XGetKeyboardMapping(self.display, keycode, 1, &mut keysyms_per_keycode);
+            let original = std::slice::from_raw_parts(raw, keysyms_per_keycode as usize).to_vec();
+            xlib::XFree(raw as *mut _);
+            original
+        };
+
+        let mut mapping = vec![keysym; keysyms_per_keycode.max(1) as usize];
+        unsafe {
+            xlib::XChangeKeyboardMapping(
+                self.display,
+                keycode as i32,
+                keysyms_per_keycode,
+                mapping.as_mut_ptr(),
+                1,
+            );
+            xlib::XSync(self.display, xlib::False);
         }
 
+        Ok((keycode, original))
+    }
+
+    fn raw_key_event(&mut self, keycode: u8, state: ButtonState) {
         let pressed = match state {
             ButtonState::Pressed => 1,
             ButtonState::Released => 0,
         };
         unsafe {
-            xtest::XTestFakeButtonEvent(self.display, button, pressed, 0);
+            xtest::XTestFakeKeyEvent(self.display, keycode as u32, pressed, 0);
+        }
+    }
+
+    /// Reposition the pointer, absolutely or relative to its current position
+    pub fn move_pointer(&mut self, x: i32, y: i32, relative: bool) -> Result<(), Error> {
+        unsafe {
+            if relative {
+                xtest::XTestFakeRelativeMotionEvent(self.display, x, y, 0, 0);
+            } else {
+                xtest::XTestFakeMotionEvent(self.display, -1, x, y, 0);
+            }
         }
 
         self.flush();
@@ -155,15 +584,677 @@ impl Display {
         Ok(())
     }
 
+    /// Capture key/button/motion events via the XRecord extension, writing
+    /// each one to `path` in the same instruction format `play` consumes.
+    /// Recording stops once `stop_key` is pressed
+    pub fn record(&mut self, path: &std::path::Path, stop_key: &str) -> Result<(), Error> {
+        let stop_keysym = self.keysym_for(stop_key)?;
+
+        let data_display = unsafe { xlib::XOpenDisplay(std::ptr::null()) };
+        if data_display.is_null() {
+            return Err("Could not acquire XDisplay for recording".into());
+        }
+
+        let mut device_range: xrecord::XRecordRange = unsafe { std::mem::zeroed() };
+        device_range.device_events.first = xlib::KeyPress as u8;
+        device_range.device_events.last = xlib::MotionNotify as u8;
+
+        let mut ranges = [&mut device_range as *mut xrecord::XRecordRange];
+        let mut clients = [xrecord::XRecordAllClients];
+
+        let context = unsafe {
+            xrecord::XRecordCreateContext(
+                self.display,
+                0,
+                clients.as_mut_ptr(),
+                clients.len() as i32,
+                ranges.as_mut_ptr(),
+                ranges.len() as i32,
+            )
+        };
+
+        if context == 0 {
+            unsafe { xlib::XCloseDisplay(data_display) };
+            return Err("Could not create XRecord context".into());
+        }
+
+        let mut state = RecordState {
+            writer: BufWriter::new(File::create(path)?),
+            control_display: self.display,
+            context,
+            stop_keysym,
+            last_time: None,
+            error: None,
+        };
+
+        unsafe {
+            xrecord::XRecordEnableContext(
+                data_display,
+                context,
+                Some(record_callback),
+                &mut state as *mut RecordState as *mut c_char,
+            );
+
+            xrecord::XRecordFreeContext(self.display, context);
+            xlib::XCloseDisplay(data_display);
+        }
+
+        state.writer.flush()?;
+
+        if let Some(error) = state.error {
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
     pub fn flush(&mut self) {
         unsafe {
             xlib::XFlush(self.display);
         }
     }
+
+    /// Grab every source binding in `config_path` and run forever, forwarding
+    /// each matched key/button event as its mapped target event
+    pub fn listen(&mut self, config_path: &std::path::Path) -> Result<(), Error> {
+        let bindings = Binding::parse_file(config_path)?;
+        let root = unsafe { xlib::XDefaultRootWindow(self.display) };
+
+        for binding in &bindings {
+            match &binding.source {
+                Endpoint::Key(key) => {
+                    let keysym = self.keysym_for(key)?;
+                    let keycode = unsafe { xlib::XKeysymToKeycode(self.display, keysym) };
+                    if keycode == 0 {
+                        return Err(format!("Keycode for keysym of '{}' not found", key).into());
+                    }
+
+                    unsafe {
+                        xlib::XGrabKey(
+                            self.display,
+                            keycode as i32,
+                            xlib::AnyModifier,
+                            root,
+                            xlib::True,
+                            xlib::GrabModeAsync,
+                            xlib::GrabModeAsync,
+                        );
+                    }
+                    self.grabs.push(Grab::Key(keycode));
+                }
+                Endpoint::Button(button) => {
+                    self.validate_button(*button)?;
+
+                    unsafe {
+                        xlib::XGrabButton(
+                            self.display,
+                            *button,
+                            xlib::AnyModifier,
+                            root,
+                            xlib::True,
+                            (xlib::ButtonPressMask | xlib::ButtonReleaseMask) as u32,
+                            xlib::GrabModeAsync,
+                            xlib::GrabModeAsync,
+                            0,
+                            0,
+                        );
+                    }
+                    self.grabs.push(Grab::Button(*button));
+                }
+            }
+        }
+
+        unsafe {
+            xlib::XkbSetDetectableAutoRepeat(self.display, xlib::True, std::ptr::null_mut());
+        }
+
+        loop {
+            let mut event: xlib::XEvent = unsafe { std::mem::zeroed() };
+            unsafe {
+                xlib::XNextEvent(self.display, &mut event);
+            }
+
+            let event_type = unsafe { event.type_ };
+            let (is_key, detail, state) = match event_type {
+                xlib::KeyPress => (true, unsafe { event.key.keycode }, ButtonState::Pressed),
+                xlib::KeyRelease => (true, unsafe { event.key.keycode }, ButtonState::Released),
+                xlib::ButtonPress => (
+                    false,
+                    unsafe { event.button.button },
+                    ButtonState::Pressed,
+                ),
+                xlib::ButtonRelease => (
+                    false,
+                    unsafe { event.button.button },
+                    ButtonState::Released,
+                ),
+                _ => continue,
+            };
+
+            for binding in &bindings {
+                let matched = match &binding.source {
+                    Endpoint::Key(key) if is_key => {
+                        let keysym = self.keysym_for(key)?;
+                        unsafe { xlib::XKeysymToKeycode(self.display, keysym) as u32 == detail }
+                    }
+                    Endpoint::Button(button) if !is_key => *button == detail,
+                    _ => false,
+                };
+
+                if matched {
+                    match &binding.target {
+                        Endpoint::Key(key) => self.send_key(key, state, &[])?,
+                        Endpoint::Button(button) => self.send_button(*button, state)?,
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn restore_keysym_mapping(display: *mut xlib::Display, keycode: u8, mut original: Vec<xlib::KeySym>) {
+    unsafe {
+        xlib::XChangeKeyboardMapping(
+            display,
+            keycode as i32,
+            original.len() as i32,
+            original.as_mut_ptr(),
+            1,
+        );
+        xlib::XSync(display, xlib::False);
+    }
+}
+
+/// Restores a keycode's prior keysym mapping when dropped, so a temporary
+/// binding from `bind_temporary_keysym` can't leak past an early error return
+struct TemporaryKeysymGuard {
+    display: *mut xlib::Display,
+    keycode: u8,
+    original: Vec<xlib::KeySym>,
+}
+
+impl Drop for TemporaryKeysymGuard {
+    fn drop(&mut self) {
+        restore_keysym_mapping(self.display, self.keycode, std::mem::take(&mut self.original));
+    }
+}
+
+/// Parse a '+' separated `--mods` chord (e.g. "ctrl+shift+super") into the
+/// key names `send_key` expects for each modifier
+fn parse_modifiers(text: &str) -> Result<Vec<&str>, Error> {
+    text.split('+')
+        .map(|part| match part.trim().to_lowercase().as_str() {
+            "ctrl" | "control" => Ok("Control_L"),
+            "shift" => Ok("Shift_L"),
+            "super" | "mod4" => Ok("Super_L"),
+            "alt" | "mod1" => Ok("Alt_L"),
+            other => Err(format!("Unknown modifier '{}'", other).into()),
+        })
+        .collect()
+}
+
+/// One side of a remap binding, either a named key or a numbered mouse button
+#[derive(Clone, Debug)]
+enum Endpoint {
+    Key(String),
+    Button(u32),
+}
+
+impl Endpoint {
+    fn parse(text: &str) -> Result<Self, Error> {
+        let mut parts = text.trim().splitn(2, '=');
+        let kind = parts.next().unwrap_or_default().trim();
+        let value = parts
+            .next()
+            .ok_or("Expected 'key=<name>' or 'button=<n>'")?
+            .trim();
+
+        match kind {
+            "key" => Ok(Endpoint::Key(value.to_string())),
+            "button" => Ok(Endpoint::Button(value.parse()?)),
+            other => Err(format!("Unknown binding kind '{}'", other).into()),
+        }
+    }
+}
+
+/// A single `--listen` config line mapping a source event to a target event,
+/// e.g. `key=Caps_Lock -> key=Escape`
+#[derive(Clone, Debug)]
+struct Binding {
+    source: Endpoint,
+    target: Endpoint,
+}
+
+impl Binding {
+    fn parse_file(path: &std::path::Path) -> Result<Vec<Self>, Error> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+
+        let mut bindings = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut sides = line.splitn(2, "->");
+            let source = sides.next().unwrap_or_default();
+            let target = sides
+                .next()
+                .ok_or_else(|| format!("Line {}: Missing '->' separator", number + 1))?;
+
+            let source = Endpoint::parse(source)
+                .map_err(|err| format!("Line {}: {}", number + 1, err))?;
+            let target = Endpoint::parse(target)
+                .map_err(|err| format!("Line {}: {}", number + 1, err))?;
+
+            bindings.push(Binding { source, target });
+        }
+
+        Ok(bindings)
+    }
+}
+
+/// An active grab held by a `--listen` session, ungrabbed on `Drop`
+#[derive(Clone, Copy, Debug)]
+enum Grab {
+    Key(xlib::KeyCode),
+    Button(u32),
+}
+
+struct RecordState {
+    writer: BufWriter<File>,
+    control_display: *mut xlib::Display,
+    context: xrecord::XRecordContext,
+    stop_keysym: xlib::KeySym,
+    last_time: Option<u32>,
+    error: Option<Error>,
+}
+
+/// Translate a keycode back to the key name expected by `KeyStrPress`/
+/// `KeyStrRelease` lines, mirroring the lookup `Display::dump` uses
+fn keycode_name(display: *mut xlib::Display, keycode: u8) -> Option<String> {
+    unsafe {
+        let keysym = xlib::XKeycodeToKeysym(display, keycode, 0);
+        let name = xlib::XKeysymToString(keysym);
+        if name.is_null() {
+            return None;
+        }
+
+        std::ffi::CStr::from_ptr(name).to_str().ok().map(String::from)
+    }
+}
+
+extern "C" fn record_callback(closure: *mut c_char, data: *mut xrecord::XRecordInterceptData) {
+    let state = unsafe { &mut *(closure as *mut RecordState) };
+    let data_ref = unsafe { &*data };
+
+    if data_ref.category == xrecord::XRecordFromServer {
+        if let Err(err) = record_event(state, data_ref) {
+            state.error = Some(err);
+            // Don't just latch the error and go quiet: with nothing left to
+            // disable the context, XRecordEnableContext would block forever,
+            // even past the configured stop key
+            unsafe {
+                xrecord::XRecordDisableContext(state.control_display, state.context);
+                xlib::XFlush(state.control_display);
+            }
+        }
+    }
+
+    unsafe {
+        xrecord::XRecordFreeData(data);
+    }
+}
+
+fn record_event(state: &mut RecordState, data: &xrecord::XRecordInterceptData) -> Result<(), Error> {
+    if data.data_len == 0 {
+        return Ok(());
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(data.data, (data.data_len as usize) * 4) };
+    let event_type = bytes[0] & 0x7f;
+    let detail = bytes[1];
+    let time = u32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+    if let Some(last_time) = state.last_time {
+        let delay = time.saturating_sub(last_time);
+        if delay > 0 {
+            writeln!(state.writer, "Delay {}", delay)?;
+        }
+    }
+    state.last_time = Some(time);
+
+    match event_type as i32 {
+        xlib::KeyPress | xlib::KeyRelease => {
+            let keysym = unsafe { xlib::XKeycodeToKeysym(state.control_display, detail, 0) };
+            // A key with no keysym name can't be written as a KeyStrPress/
+            // KeyStrRelease line; skip recording it rather than failing the
+            // whole session over one unnamable key
+            let name = match keycode_name(state.control_display, detail) {
+                Some(name) => name,
+                None => return Ok(()),
+            };
+
+            if event_type as i32 == xlib::KeyPress {
+                writeln!(state.writer, "KeyStrPress {}", name)?;
+            } else {
+                writeln!(state.writer, "KeyStrRelease {}", name)?;
+            }
+
+            if event_type as i32 == xlib::KeyPress && keysym == state.stop_keysym {
+                unsafe {
+                    xrecord::XRecordDisableContext(state.control_display, state.context);
+                    xlib::XFlush(state.control_display);
+                }
+            }
+        }
+        xlib::ButtonPress => writeln!(state.writer, "ButtonPress {}", detail)?,
+        xlib::ButtonRelease => writeln!(state.writer, "ButtonRelease {}", detail)?,
+        xlib::MotionNotify => {
+            let x = i16::from_ne_bytes([bytes[20], bytes[21]]);
+            let y = i16::from_ne_bytes([bytes[22], bytes[23]]);
+            writeln!(state.writer, "MotionNotify {} {}", x, y)?;
+        }
+        _ => {}
+    }
+
+    Ok(())
 }
 
 impl std::ops::Drop for Display {
     fn drop(&mut self) {
-        unsafe { xlib::XCloseDisplay(self.display) };
+        unsafe {
+            let root = xlib::XDefaultRootWindow(self.display);
+            for grab in &self.grabs {
+                match grab {
+                    Grab::Key(keycode) => {
+                        xlib::XUngrabKey(self.display, *keycode as i32, xlib::AnyModifier, root);
+                    }
+                    Grab::Button(button) => {
+                        xlib::XUngrabButton(self.display, *button, xlib::AnyModifier, root);
+                    }
+                }
+            }
+
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+/// Which event-sending surface `--key`/`--mouse` are synthesized through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackendKind {
+    X11,
+    Uinput,
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "x11" => Ok(BackendKind::X11),
+            "uinput" => Ok(BackendKind::Uinput),
+            other => Err(format!("Unknown backend '{}'", other).into()),
+        }
+    }
+}
+
+/// The shared surface a single key/button event can be sent through,
+/// implemented once per backend (X11's XTest extension, Linux's uinput)
+trait Backend {
+    fn send_key(&mut self, key: &str, state: ButtonState) -> Result<(), Error>;
+    fn send_button(&mut self, button: u32, state: ButtonState) -> Result<(), Error>;
+    fn flush(&mut self);
+}
+
+impl Backend for Display {
+    fn send_key(&mut self, key: &str, state: ButtonState) -> Result<(), Error> {
+        Display::send_key(self, key, state, &[])
+    }
+
+    fn send_button(&mut self, button: u32, state: ButtonState) -> Result<(), Error> {
+        Display::send_button(self, button, state)
+    }
+
+    fn flush(&mut self) {
+        Display::flush(self)
+    }
+}
+
+// Empirically enough for libinput/compositors to pick up a newly created
+// uinput device; too short and the very first event sent can be dropped
+const UINPUT_SETTLE_DELAY: Duration = Duration::from_millis(150);
+
+const UI_SET_EVBIT: libc::c_ulong = 0x4004_5564;
+const UI_SET_KEYBIT: libc::c_ulong = 0x4004_5565;
+const UI_DEV_CREATE: libc::c_ulong = 0x5501;
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_SYN: u16 = 0x00;
+const SYN_REPORT: u16 = 0;
+const BUS_USB: u16 = 0x03;
+const UINPUT_MAX_NAME_SIZE: usize = 80;
+const ABS_CNT: usize = 64;
+
+#[repr(C)]
+struct InputId {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+}
+
+#[repr(C)]
+struct UinputUserDev {
+    name: [u8; UINPUT_MAX_NAME_SIZE],
+    id: InputId,
+    ff_effects_max: u32,
+    absmax: [i32; ABS_CNT],
+    absmin: [i32; ABS_CNT],
+    absfuzz: [i32; ABS_CNT],
+    absflat: [i32; ABS_CNT],
+}
+
+#[repr(C)]
+struct InputEvent {
+    time: libc::timeval,
+    kind: u16,
+    code: u16,
+    value: i32,
+}
+
+/// Linux input event codes for the subset of keys xlib's keysym names
+/// already cover; extend as more keys are needed
+const KEY_CODES: &[(&str, u16)] = &[
+    ("a", 30), ("b", 48), ("c", 46), ("d", 32), ("e", 18), ("f", 33),
+    ("g", 34), ("h", 35), ("i", 23), ("j", 36), ("k", 37), ("l", 38),
+    ("m", 50), ("n", 49), ("o", 24), ("p", 25), ("q", 16), ("r", 19),
+    ("s", 31), ("t", 20), ("u", 22), ("v", 47), ("w", 17), ("x", 45),
+    ("y", 21), ("z", 44),
+    ("0", 11), ("1", 2), ("2", 3), ("3", 4), ("4", 5), ("5", 6),
+    ("6", 7), ("7", 8), ("8", 9), ("9", 10),
+    ("space", 57),
+    ("Return", 28),
+    ("Escape", 1),
+    ("Tab", 15),
+    ("BackSpace", 14),
+    ("Delete", 111),
+    ("Up", 103),
+    ("Down", 108),
+    ("Left", 105),
+    ("Right", 106),
+    ("Shift_L", 42),
+    ("Shift_R", 54),
+    ("Control_L", 29),
+    ("Control_R", 97),
+    ("Alt_L", 56),
+    ("Alt_R", 100),
+    ("Super_L", 125),
+    ("Super_R", 126),
+];
+
+/// Linux input event codes for the mouse buttons `--mouse` accepts
+const BUTTON_CODES: &[(u32, u16)] = &[(1, 0x110), (2, 0x112), (3, 0x111), (8, 0x113), (9, 0x114)];
+
+/// Writes synthesized key/button events to a virtual `/dev/uinput` device so
+/// they reach the kernel input layer directly, bypassing X11 entirely
+pub struct UinputBackend {
+    file: File,
+}
+
+impl UinputBackend {
+    pub fn new() -> Result<Self, Error> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/uinput")?;
+        let fd = file.as_raw_fd();
+
+        unsafe {
+            if libc::ioctl(fd, UI_SET_EVBIT, EV_KEY as libc::c_int) < 0 {
+                return Err("Failed to enable EV_KEY on uinput device".into());
+            }
+
+            // Registered for parity with a hardware keyboard/mouse even
+            // though Backend has no motion method yet; lets the device grow
+            // relative-motion support later without another UI_SET_EVBIT
+            if libc::ioctl(fd, UI_SET_EVBIT, EV_REL as libc::c_int) < 0 {
+                return Err("Failed to enable EV_REL on uinput device".into());
+            }
+
+            for &(_, code) in KEY_CODES.iter() {
+                if libc::ioctl(fd, UI_SET_KEYBIT, code as libc::c_int) < 0 {
+                    return Err(format!("Failed to register key code {}", code).into());
+                }
+            }
+            for &(_, code) in BUTTON_CODES.iter() {
+                if libc::ioctl(fd, UI_SET_KEYBIT, code as libc::c_int) < 0 {
+                    return Err(format!("Failed to register button code {}", code).into());
+                }
+            }
+        }
+
+        let mut name = [0u8; UINPUT_MAX_NAME_SIZE];
+        for (dst, src) in name.iter_mut().zip(b"key-forward".iter()) {
+            *dst = *src;
+        }
+
+        let dev = UinputUserDev {
+            name,
+            id: InputId {
+                bustype: BUS_USB,
+                vendor: 0x1,
+                product: 0x1,
+                version: 1,
+            },
+            ff_effects_max: 0,
+            absmax: [0; ABS_CNT],
+            absmin: [0; ABS_CNT],
+            absfuzz: [0; ABS_CNT],
+            absflat: [0; ABS_CNT],
+        };
+
+        unsafe {
+            let dev_bytes = std::slice::from_raw_parts(
+                &dev as *const UinputUserDev as *const u8,
+                std::mem::size_of::<UinputUserDev>(),
+            );
+            (&file).write_all(dev_bytes)?;
+
+            if libc::ioctl(fd, UI_DEV_CREATE) < 0 {
+                return Err("Failed to create uinput device".into());
+            }
+        }
+
+        // Give the compositor/libinput time to notice and open the freshly
+        // created device; events written before that happens are delivered
+        // to nobody and silently lost
+        sleep(UINPUT_SETTLE_DELAY);
+
+        Ok(UinputBackend { file })
+    }
+
+    fn keycode_for(&self, key: &str) -> Result<u16, Error> {
+        KEY_CODES
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, code)| *code)
+            .ok_or_else(|| format!("No uinput mapping for key '{}'", key).into())
+    }
+
+    fn write_event(&mut self, kind: u16, code: u16, value: i32) -> Result<(), Error> {
+        let event = InputEvent {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            kind,
+            code,
+            value,
+        };
+
+        unsafe {
+            let event_bytes = std::slice::from_raw_parts(
+                &event as *const InputEvent as *const u8,
+                std::mem::size_of::<InputEvent>(),
+            );
+            self.file.write_all(event_bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for UinputBackend {
+    fn send_key(&mut self, key: &str, state: ButtonState) -> Result<(), Error> {
+        let code = self.keycode_for(key)?;
+        let value = match state {
+            ButtonState::Pressed => 1,
+            ButtonState::Released => 0,
+        };
+
+        self.write_event(EV_KEY, code, value)?;
+        self.flush();
+
+        Ok(())
+    }
+
+    fn send_button(&mut self, button: u32, state: ButtonState) -> Result<(), Error> {
+        let code = BUTTON_CODES
+            .iter()
+            .find(|(index, _)| *index == button)
+            .map(|(_, code)| *code)
+            .ok_or_else(|| format!("No uinput mapping for mouse button '{}'", button))?;
+        let value = match state {
+            ButtonState::Pressed => 1,
+            ButtonState::Released => 0,
+        };
+
+        self.write_event(EV_KEY, code, value)?;
+        self.flush();
+
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        let _ = self.write_event(EV_SYN, SYN_REPORT, 0);
+    }
+}
+
+impl std::ops::Drop for UinputBackend {
+    fn drop(&mut self) {
+        // Mirror the settle delay from `new`: tearing the device down in the
+        // same instant the last event is written can still beat the
+        // consumer to it, losing that event just like an unsettled create
+        sleep(UINPUT_SETTLE_DELAY);
+
+        unsafe {
+            libc::ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY);
+        }
     }
 }